@@ -0,0 +1,160 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::os::unix::fs::{symlink, MetadataExt};
+use std::path::Path;
+
+use clap::ValueEnum;
+use walkdir::DirEntry;
+
+use crate::hash::HashType;
+
+// What to do with the redundant copies in a duplicate group once one file is kept.
+#[derive(PartialEq, Eq, ValueEnum, Clone, Copy)]
+pub enum Action {
+    Print,
+    Delete,
+    Hardlink,
+    Symlink,
+}
+
+// Which file in a duplicate group to keep; the rest are candidates for `Action`.
+#[derive(ValueEnum, Clone, Copy)]
+pub enum KeepStrategy {
+    Oldest,
+    Newest,
+    ShortestPath,
+}
+
+fn mtime_nanos(entry: &DirEntry) -> i64 {
+    let Ok(metadata) = entry.metadata() else {
+        return 0;
+    };
+    return metadata.mtime_nsec() + metadata.mtime() * 1_000_000_000;
+}
+
+// Picks the index in `group` to keep according to `keep`.
+fn keeper_index(group: &[DirEntry], keep: KeepStrategy) -> usize {
+    let better = |a: &DirEntry, b: &DirEntry| -> bool {
+        match keep {
+            KeepStrategy::Oldest => mtime_nanos(a) < mtime_nanos(b),
+            KeepStrategy::Newest => mtime_nanos(a) > mtime_nanos(b),
+            KeepStrategy::ShortestPath => {
+                a.path().as_os_str().len() < b.path().as_os_str().len()
+            }
+        }
+    };
+
+    let mut best = 0;
+    for i in 1..group.len() {
+        if better(&group[i], &group[best]) {
+            best = i;
+        }
+    }
+    return best;
+}
+
+// Hashes the whole file, independent of whatever prefix the detection stages matched on.
+fn digest_full(path: &Path, hash_type: HashType) -> Option<Vec<u8>> {
+    let file = fs::File::open(path).ok()?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = hash_type.new_hasher();
+    loop {
+        let buffer = reader.fill_buf().ok()?;
+        let length = buffer.len();
+        if length == 0 {
+            break;
+        }
+        hasher.update(buffer);
+        reader.consume(length);
+    }
+    return Some(hasher.digest());
+}
+
+// Re-hashes every file in the group end to end so an action is never applied on the strength
+// of a fuzzy (prefix-only) match alone. Always verifies with Sha1 regardless of the detection
+// `--hash`: a fast non-cryptographic checksum like Crc32 is fine for grouping candidates, but
+// isn't a safe sole gate before deleting a file or replacing it with a link.
+fn verify_identical(group: &[DirEntry]) -> bool {
+    let Some(reference) = group.first().and_then(|e| digest_full(e.path(), HashType::Sha1)) else {
+        return false;
+    };
+    return group
+        .iter()
+        .all(|e| digest_full(e.path(), HashType::Sha1).as_ref() == Some(&reference));
+}
+
+// Atomically replaces `target` with a hardlink/symlink to `keeper` via a temp file + rename, so
+// a crash mid-operation never leaves `target` missing without a replacement in its place.
+fn replace_with_link(action: Action, keeper: &Path, target: &Path) -> io::Result<()> {
+    let file_name = target.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+    let tmp_path = target.with_file_name(format!(".{file_name}.dupe-tmp"));
+
+    match action {
+        Action::Hardlink => fs::hard_link(keeper, &tmp_path)?,
+        Action::Symlink => symlink(keeper, &tmp_path)?,
+        Action::Print | Action::Delete => unreachable!(),
+    }
+    fs::rename(&tmp_path, target)?;
+    return Ok(());
+}
+
+// Applies `action` to a duplicate group: verifies the files are truly identical, keeps one
+// according to `keep`, and deletes/links the rest. A file that fails doesn't stop the rest of
+// the group from being processed; its error is reported against its own path and it's simply
+// not counted as reclaimed. Returns the number of bytes reclaimed.
+pub fn apply(action: Action, keep: KeepStrategy, group: &[DirEntry]) -> u64 {
+    if action == Action::Print || group.len() < 2 {
+        return 0;
+    }
+    if !verify_identical(group) {
+        eprintln!(
+            "Skipping group that failed content verification: {}",
+            group[0].path().display()
+        );
+        return 0;
+    }
+
+    let keeper = keeper_index(group, keep);
+    // Only Hardlink/Symlink need the keeper's path, so resolve it lazily - Delete never touches
+    // it and shouldn't be blocked by a keeper path that fails to resolve.
+    //
+    // A symlink target is resolved relative to the symlink's own directory, not the process's
+    // CWD, so a bare path as collected from the walk (relative to whatever `root` the caller
+    // passed) produces a dangling link unless `root` itself happened to be absolute.
+    // Canonicalizing makes the link correct regardless of `root` or which directory `target`
+    // lives in.
+    let keeper_path = match action {
+        Action::Hardlink | Action::Symlink => match group[keeper].path().canonicalize() {
+            Ok(path) => Some(path),
+            Err(err) => {
+                eprintln!(
+                    "Skipping group: failed to resolve keeper path {}: {err}",
+                    group[keeper].path().display()
+                );
+                return 0;
+            }
+        },
+        Action::Print | Action::Delete => None,
+    };
+
+    let mut reclaimed = 0;
+    for (i, entry) in group.iter().enumerate() {
+        if i == keeper {
+            continue;
+        }
+        let result = match action {
+            Action::Delete => fs::remove_file(entry.path()),
+            Action::Hardlink | Action::Symlink => replace_with_link(
+                action,
+                keeper_path.as_deref().expect("keeper path resolved for link actions"),
+                entry.path(),
+            ),
+            Action::Print => unreachable!(),
+        };
+        match result {
+            Ok(()) => reclaimed += entry.metadata().map(|m| m.size()).unwrap_or(0),
+            Err(err) => eprintln!("Failed to apply action to {}: {err}", entry.path().display()),
+        }
+    }
+    return reclaimed;
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+const CACHE_FILE_NAME: &str = "dupe/hash_cache.bin";
+
+// The file attributes a cached digest was computed against. If the file's current
+// size or modification time no longer match, the entry is stale and must be recomputed.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+struct CacheKey {
+    size: u64,
+    mtime_nanos: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct CacheEntry {
+    key: CacheKey,
+    // Digests already computed for this file, keyed by (hash algorithm, cumulative byte offset
+    // hashed up to) so switching `--hash` between runs can't return another algorithm's digest.
+    digests: HashMap<(String, u64), Vec<u8>>,
+}
+
+// On-disk cache mapping an absolute path to the digests computed for it, so that re-scanning
+// a mostly-unchanged directory tree does not re-read and re-hash files it has already seen.
+#[derive(Serialize, Deserialize, Default)]
+pub struct HashCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl HashCache {
+    fn path() -> Option<PathBuf> {
+        return dirs::cache_dir().map(|dir| dir.join(CACHE_FILE_NAME));
+    }
+
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return HashCache::default();
+        };
+        let Ok(file) = File::open(path) else {
+            return HashCache::default();
+        };
+        return bincode::deserialize_from(file).unwrap_or_default();
+    }
+
+    pub fn save(&self) {
+        if !self.dirty {
+            return;
+        }
+        let Some(path) = Self::path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            if std::fs::create_dir_all(parent).is_err() {
+                return;
+            }
+        }
+        if let Ok(file) = File::create(path) {
+            let _ = bincode::serialize_into(BufWriter::new(file), self);
+        }
+    }
+
+    // Returns the cached digest for `path` hashed by `hash_namespace` up through `end_offset`
+    // bytes, if present and the file's size/mtime still match what was recorded when computed.
+    pub fn get(
+        &self,
+        path: &Path,
+        size: u64,
+        mtime_nanos: i64,
+        hash_namespace: &str,
+        end_offset: u64,
+    ) -> Option<Vec<u8>> {
+        let entry = self.entries.get(path)?;
+        if entry.key != (CacheKey { size, mtime_nanos }) {
+            return None;
+        }
+        return entry
+            .digests
+            .get(&(hash_namespace.to_owned(), end_offset))
+            .cloned();
+    }
+
+    pub fn insert(
+        &mut self,
+        path: PathBuf,
+        size: u64,
+        mtime_nanos: i64,
+        hash_namespace: &str,
+        end_offset: u64,
+        digest: Vec<u8>,
+    ) {
+        let key = CacheKey { size, mtime_nanos };
+        let entry = self.entries.entry(path).or_insert_with(|| CacheEntry {
+            key: key.clone(),
+            digests: HashMap::new(),
+        });
+        // Stale digests from a previous version of the file are invalid under the new key
+        if entry.key != key {
+            entry.key = key;
+            entry.digests.clear();
+        }
+        entry
+            .digests
+            .insert((hash_namespace.to_owned(), end_offset), digest);
+        self.dirty = true;
+    }
+}
@@ -1,18 +1,27 @@
+mod actions;
+mod cache;
+mod hash;
+mod report;
 mod size;
-use sha1::{Digest, Sha1};
-use size::Size;
+use actions::{Action, KeepStrategy};
+use cache::HashCache;
+use hash::{ContentHasher, HashType};
+use size::{Size, Units};
 
 use std::fs::File;
 use std::hash::Hash;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::Mutex;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{HashMap, HashSet},
     os::unix::fs::MetadataExt,
     path::PathBuf,
 };
 
 use clap::{Parser, ValueEnum};
+use dashmap::DashMap;
+use rayon::prelude::*;
 use walkdir::{DirEntry, WalkDir};
 
 #[derive(PartialEq, Eq, ValueEnum, Clone)]
@@ -24,6 +33,13 @@ enum Algorithm {
     FullContent,
 }
 
+#[derive(PartialEq, Eq, ValueEnum, Clone, Copy)]
+enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -34,61 +50,235 @@ struct Args {
 
     #[arg(value_enum, long, default_value_t = Algorithm::Name)]
     algorithm: Algorithm,
+
+    #[arg(value_enum, long = "hash", default_value_t = HashType::Sha1)]
+    hash_type: HashType,
+
+    /// Comma-separated list of extensions to allow (e.g. "jpg,png,mp4")
+    #[arg(long, value_delimiter = ',')]
+    allowed_extensions: Vec<String>,
+
+    /// Comma-separated list of extensions to exclude (e.g. "tmp,log")
+    #[arg(long, value_delimiter = ',')]
+    excluded_extensions: Vec<String>,
+
+    #[arg(value_enum, long, default_value_t = Action::Print)]
+    action: Action,
+
+    #[arg(value_enum, long, default_value_t = KeepStrategy::ShortestPath)]
+    keep: KeepStrategy,
+
+    #[arg(value_enum, long, default_value_t = Format::Text)]
+    format: Format,
+
+    #[arg(value_enum, long, default_value_t = Units::Decimal)]
+    units: Units,
+}
+
+// A set of extensions to match a DirEntry against in O(1) per file, built once before traversal
+struct ExtensionMatcher {
+    allowed: HashSet<String>,
+    excluded: HashSet<String>,
+}
+
+impl ExtensionMatcher {
+    fn new(allowed_extensions: &[String], excluded_extensions: &[String]) -> Self {
+        let normalize = |extensions: &[String]| -> HashSet<String> {
+            extensions
+                .iter()
+                .map(|ext| ext.trim().to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect()
+        };
+        return ExtensionMatcher {
+            allowed: normalize(allowed_extensions),
+            excluded: normalize(excluded_extensions),
+        };
+    }
+
+    fn matches(&self, entry: &DirEntry) -> bool {
+        let extension = entry
+            .path()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .unwrap_or_default();
+
+        if !self.allowed.is_empty() && !self.allowed.contains(&extension) {
+            return false;
+        }
+        return !self.excluded.contains(&extension);
+    }
 }
 
-// Compute the SHA1 hash of reading the first n bytes of the file or all of the file
-fn sha1_read(path: &Path, bytes_limit: u16) -> Option<Vec<u8>> {
-    let file = File::open(path).ok()?;
-    let mut reader = BufReader::new(file);
-    if bytes_limit > 0 {
-        let mut buffer = vec![0; bytes_limit as usize];
-        reader.read(&mut buffer).ok();
-        return Some(Sha1::new_with_prefix(buffer).finalize()[..].to_owned());
+// Disjoint block boundaries content matching reads up to, each only once. A group that has
+// already diverged by an earlier boundary is dropped before the next, larger block is read.
+const FIRST_BLOCK: u64 = 4 * 1024;
+const SECOND_BLOCK: u64 = 64 * 1024;
+
+// Carries the running digest and byte offset across content-matching stages so a later, larger
+// block only hashes the bytes past what an earlier stage already read. Deliberately holds no
+// open file handle between stages: with one file per group member, keeping every handle open
+// for the rest of the run exhausts the process's fd limit on large duplicate sets.
+struct IncrementalHasher {
+    hasher: Box<dyn ContentHasher>,
+    bytes_read: u64,
+}
+
+impl IncrementalHasher {
+    fn new(hash_type: HashType) -> Self {
+        return IncrementalHasher {
+            hasher: hash_type.new_hasher(),
+            bytes_read: 0,
+        };
     }
 
-    // If limit was provided as 0 then process all of the file
-    let mut hasher = Sha1::new();
-    loop {
-        let buffer = reader.fill_buf().unwrap_or(&[]);
-        let length = buffer.len();
-        if length == 0 {
-            break;
+    // Reopens `path`, seeks past what's already been hashed, and reads forward until
+    // `end_offset` bytes have been consumed in total (or the file is exhausted), returning
+    // the digest of everything read so far. The handle is only open for this call.
+    fn digest_up_to(&mut self, path: &Path, end_offset: u64) -> io::Result<Vec<u8>> {
+        if self.bytes_read < end_offset {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::Start(self.bytes_read))?;
+            let mut reader = BufReader::new(file);
+            while self.bytes_read < end_offset {
+                let buffer = reader.fill_buf()?;
+                if buffer.is_empty() {
+                    break;
+                }
+                let take = buffer.len().min((end_offset - self.bytes_read) as usize);
+                self.hasher.update(&buffer[..take]);
+                reader.consume(take);
+                self.bytes_read += take as u64;
+            }
+        }
+        return Ok(self.hasher.digest());
+    }
+}
+
+// Hashes `entry` up through `end_offset` bytes using `hash_type`, reusing the running digest
+// kept in `states` across stages, and consulting `cache` first so an unchanged file is never
+// re-read across runs. Both are shared across the parallel hashing workers in
+// `find_duplicates_by`. A file that can no longer be opened or read is reported to stderr and
+// treated as not a duplicate, rather than silently dropped.
+fn hash_content(
+    entry: &DirEntry,
+    end_offset: u64,
+    hash_type: HashType,
+    states: &DashMap<PathBuf, IncrementalHasher>,
+    cache: &Mutex<HashCache>,
+) -> Option<Vec<u8>> {
+    let metadata = entry.metadata().ok()?;
+    let size = metadata.size();
+    let mtime_nanos = metadata.mtime_nsec() + metadata.mtime() * 1_000_000_000;
+    let end_offset = end_offset.min(size);
+    let hash_namespace = hash_type.cache_namespace();
+    // Canonicalize before touching the cache: the key must identify the file on disk, not
+    // whatever relative/symlinked `root` the caller happened to pass on this invocation, or
+    // two different files that coincidentally land on the same relative path across runs
+    // (e.g. run from different working directories) can be served each other's stale digest.
+    let canonical_path = entry.path().canonicalize().ok()?;
+
+    if let Some(digest) = cache.lock().unwrap().get(
+        &canonical_path,
+        size,
+        mtime_nanos,
+        hash_namespace,
+        end_offset,
+    ) {
+        return Some(digest);
+    }
+
+    states
+        .entry(entry.path().to_owned())
+        .or_insert_with(|| IncrementalHasher::new(hash_type));
+    let digest = match states.get_mut(entry.path())?.digest_up_to(entry.path(), end_offset) {
+        Ok(digest) => digest,
+        Err(err) => {
+            eprintln!("Failed to read {}: {err}", entry.path().display());
+            return None;
+        }
+    };
+
+    cache.lock().unwrap().insert(
+        canonical_path,
+        size,
+        mtime_nanos,
+        hash_namespace,
+        end_offset,
+        digest.clone(),
+    );
+    return Some(digest);
+}
+
+// Drops `states` entries for files that were in `before` but didn't make it into `after`,
+// i.e. files ruled out of every live group by this stage. Keeps the map from growing forever
+// across a run over a large tree.
+fn evict_dropped(
+    states: &DashMap<PathBuf, IncrementalHasher>,
+    before: &[Vec<DirEntry>],
+    after: &[Vec<DirEntry>],
+) {
+    let kept: HashSet<&Path> = after.iter().flatten().map(|e| e.path()).collect();
+    for entry in before.iter().flatten() {
+        if !kept.contains(entry.path()) {
+            states.remove(entry.path());
         }
-        hasher.update(buffer);
-        reader.consume(length);
     }
-    return Some(hasher.finalize()[..].to_owned());
 }
 
-// Groups a vector of DirEntry according to the result of the closure f
-fn find_duplicates_by<F, K>(files: Vec<DirEntry>, f: F) -> impl Iterator<Item = Vec<DirEntry>>
+// Groups a vector of DirEntry according to the result of the closure f, hashing files
+// within the group in parallel since each file's key is independent of the others.
+fn find_duplicates_by<F, K>(files: Vec<DirEntry>, f: F) -> Vec<Vec<DirEntry>>
 where
-    F: Fn(&DirEntry) -> Option<K>,
-    K: Eq + Hash,
+    F: Fn(&DirEntry) -> Option<K> + Sync,
+    K: Eq + Hash + Send,
 {
     return files
-        .into_iter()
-        .fold(HashMap::new(), |mut map, entry| {
-            f(&entry).map(|key| map.entry(key).or_insert(Vec::new()).push(entry));
+        .into_par_iter()
+        .fold(HashMap::new, |mut map: HashMap<K, Vec<DirEntry>>, entry| {
+            if let Some(key) = f(&entry) {
+                map.entry(key).or_insert_with(Vec::new).push(entry);
+            }
             return map;
         })
+        .reduce(HashMap::new, |mut a, b| {
+            for (key, mut group) in b {
+                a.entry(key).or_insert_with(Vec::new).append(&mut group);
+            }
+            return a;
+        })
         .into_values()
-        .filter(|g| g.len() > 1);
+        .filter(|g| g.len() > 1)
+        .collect();
+}
+
+// Re-groups each of `groups` by the result of `f`
+fn regroup<F, K>(groups: Vec<Vec<DirEntry>>, f: F) -> Vec<Vec<DirEntry>>
+where
+    F: Fn(&DirEntry) -> Option<K> + Sync,
+    K: Eq + Hash + Send,
+{
+    return groups
+        .into_iter()
+        .flat_map(|g| find_duplicates_by(g, &f))
+        .collect();
 }
 
 fn find_duplicate_files(
     files: Vec<DirEntry>,
     algorithm: Algorithm,
+    hash_type: HashType,
+    cache: &Mutex<HashCache>,
+    content_states: &DashMap<PathBuf, IncrementalHasher>,
 ) -> impl Iterator<Item = Vec<DirEntry>> {
     let mut dupes = vec![files].into_iter();
 
+    // These are progress diagnostics, not report output, so they always go to stderr -
+    // otherwise they'd corrupt `--format json`/`csv`, which must be the only thing on stdout.
     if [Algorithm::Name, Algorithm::NameAndSize].contains(&algorithm) {
-        dupes = dupes
-            .map(|g| find_duplicates_by(g, |e| Some(e.file_name().to_owned())))
-            .flatten()
-            .collect::<Vec<_>>()
-            .into_iter();
-        println!("Found {} duplicate groups by name", dupes.len());
+        dupes = regroup(dupes.collect(), |e| Some(e.file_name().to_owned())).into_iter();
+        eprintln!("Found {} duplicate groups by name", dupes.len());
     }
     if [
         Algorithm::Size,
@@ -98,43 +288,47 @@ fn find_duplicate_files(
     ]
     .contains(&algorithm)
     {
-        dupes = dupes
-            .map(|g| find_duplicates_by(g, |e| e.metadata().map(|m| m.size()).ok()))
-            .flatten()
-            .collect::<Vec<_>>()
-            .into_iter();
-        println!("Found {} duplicate groups by size", dupes.len());
+        dupes = regroup(dupes.collect(), |e| e.metadata().map(|m| m.size()).ok()).into_iter();
+        eprintln!("Found {} duplicate groups by size", dupes.len());
     }
     if [Algorithm::FuzzyContent, Algorithm::FullContent].contains(&algorithm) {
-        dupes = dupes
-            .map(|g| find_duplicates_by(g, |e| sha1_read(e.path(), 1024)))
-            .flatten()
-            .collect::<Vec<_>>()
-            .into_iter();
-        println!("Found {} duplicate groups by first 1024 bytes", dupes.len());
-
-        dupes = dupes
-            .map(|g| find_duplicates_by(g, |e| sha1_read(e.path(), 4096)))
-            .flatten()
-            .collect::<Vec<_>>()
-            .into_iter();
-        println!("Found {} duplicate groups by first 4096 bytes", dupes.len());
+        let before: Vec<_> = dupes.collect();
+        let after = regroup(before.clone(), |e| {
+            hash_content(e, FIRST_BLOCK, hash_type, content_states, cache)
+        });
+        evict_dropped(content_states, &before, &after);
+        eprintln!("Found {} duplicate groups by first 4KiB", after.len());
+
+        let before = after;
+        let after = regroup(before.clone(), |e| {
+            hash_content(e, SECOND_BLOCK, hash_type, content_states, cache)
+        });
+        evict_dropped(content_states, &before, &after);
+        eprintln!("Found {} duplicate groups by first 64KiB", after.len());
+        dupes = after.into_iter();
     }
 
     if [Algorithm::FullContent].contains(&algorithm) {
-        dupes = dupes
-            .map(|g| find_duplicates_by(g, |e| sha1_read(e.path(), 0)))
-            .flatten()
-            .collect::<Vec<_>>()
-            .into_iter();
-        println!(
+        let before: Vec<_> = dupes.collect();
+        let after = regroup(before.clone(), |e| {
+            hash_content(e, u64::MAX, hash_type, content_states, cache)
+        });
+        // Full content hashing is the last content stage, so nothing further will resume
+        // reading any of these files - free every remaining handle-backed state now.
+        evict_dropped(content_states, &before, &[]);
+        eprintln!(
             "Found {} duplicate groups by full content bytes",
-            dupes.len()
+            after.len()
         );
+        dupes = after.into_iter();
     }
 
-    println!();
-    return dupes;
+    // Parallel hashing processes groups out of order, so sort each group's files for
+    // deterministic output across runs.
+    return dupes.map(|mut group| {
+        group.sort_by(|a, b| a.path().cmp(b.path()));
+        return group;
+    });
 }
 
 fn main() {
@@ -142,43 +336,81 @@ fn main() {
     let root = args.root;
     let min_size = (args.min_size * 1_000_000.0) as u64;
     let algorithm = args.algorithm;
+    let hash_type = args.hash_type;
+    let action = args.action;
+    let keep = args.keep;
+    let units = args.units;
+    let extension_matcher =
+        ExtensionMatcher::new(&args.allowed_extensions, &args.excluded_extensions);
 
-    // Find all files
-    let files: Vec<_> = WalkDir::new(root)
-        .into_iter()
-        .filter_map(|e| {
-            e.ok().filter(|e| {
-                e.file_type().is_file() && e.metadata().is_ok_and(|m| m.size() > min_size)
-            })
+    // Walk the tree (sequential, since directory traversal is inherently ordered), then
+    // stat and filter the entries in parallel
+    let entries: Vec<_> = WalkDir::new(root).into_iter().filter_map(|e| e.ok()).collect();
+    let files: Vec<_> = entries
+        .into_par_iter()
+        .filter(|e| {
+            e.file_type().is_file()
+                && e.metadata().is_ok_and(|m| m.size() > min_size)
+                && extension_matcher.matches(e)
         })
         .collect();
 
-    // Find duplicate files and sort them according to size
-    let dupe_map =
-        find_duplicate_files(files, algorithm).fold(BTreeMap::new(), |mut map, group| {
-            let size = group
-                .iter()
-                .filter_map(|e| e.metadata().map(|m| m.size()).ok())
-                .sum();
+    let cache = Mutex::new(HashCache::load());
+    let content_states = DashMap::new();
 
-            map.entry(size).or_insert(group);
-            return map;
-        });
+    // Find duplicate groups and pair each with its total size, largest first. Keyed by
+    // position rather than by size - two unrelated groups can easily sum to the same total
+    // number of bytes, and a size-keyed map would silently keep only one of them.
+    let mut groups: Vec<(u64, Vec<DirEntry>)> =
+        find_duplicate_files(files, algorithm, hash_type, &cache, &content_states)
+            .map(|group| {
+                let size = group
+                    .iter()
+                    .filter_map(|e| e.metadata().map(|m| m.size()).ok())
+                    .sum();
+                return (size, group);
+            })
+            .collect();
+    groups.sort_by(|a, b| b.0.cmp(&a.0));
 
     let mut total_size = 0;
-    let total_count = dupe_map.len();
-    for (size, group) in dupe_map.into_iter().rev() {
+    let mut reclaimed_size = 0;
+    let total_count = groups.len();
+    for (size, group) in &groups {
         total_size += size;
-        println!("{}", Size::new(size));
-        for file in group {
-            println!("{}", file.into_path().display());
+
+        reclaimed_size += actions::apply(action, keep, group);
+    }
+
+    match args.format {
+        Format::Json => report::print_json(&groups, units),
+        Format::Csv => report::print_csv(&groups),
+        Format::Text => {
+            for (size, group) in &groups {
+                println!("{}", Size::new(*size, units));
+                for file in group {
+                    println!("{}", file.path().display());
+                }
+                println!();
+            }
         }
-        println!();
     }
 
-    println!(
-        "Found a total of {} duplicate groups occupying a space of {}",
-        total_count,
-        Size::new(total_size)
-    );
+    if args.format == Format::Text {
+        if action == Action::Print {
+            println!(
+                "Found a total of {} duplicate groups occupying a space of {}",
+                total_count,
+                Size::new(total_size, units)
+            );
+        } else {
+            println!(
+                "Found a total of {} duplicate groups, reclaimed {}",
+                total_count,
+                Size::new(reclaimed_size, units)
+            );
+        }
+    }
+
+    cache.into_inner().unwrap().save();
 }
@@ -0,0 +1,47 @@
+use serde::Serialize;
+use walkdir::DirEntry;
+
+use crate::size::{Size, Units};
+
+// A single duplicate group as emitted by `--format json`.
+#[derive(Serialize)]
+struct GroupReport {
+    id: usize,
+    size_bytes: u64,
+    size_human: String,
+    paths: Vec<String>,
+}
+
+pub fn print_json(groups: &[(u64, Vec<DirEntry>)], units: Units) {
+    let report: Vec<GroupReport> = groups
+        .iter()
+        .enumerate()
+        .map(|(id, (size, group))| GroupReport {
+            id,
+            size_bytes: *size,
+            size_human: Size::new(*size, units).to_string(),
+            paths: group.iter().map(|e| e.path().display().to_string()).collect(),
+        })
+        .collect();
+
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{json}"),
+        Err(err) => eprintln!("Failed to serialize report as JSON: {err}"),
+    }
+}
+
+pub fn print_csv(groups: &[(u64, Vec<DirEntry>)]) {
+    println!("group_id,size_bytes,path");
+    for (id, (size, group)) in groups.iter().enumerate() {
+        for file in group {
+            println!("{},{},{}", id, size, csv_escape(&file.path().display().to_string()));
+        }
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    return value.to_owned();
+}
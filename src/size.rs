@@ -1,46 +1,78 @@
 use std::fmt;
 
+use clap::ValueEnum;
+
+// Whether to scale byte counts using decimal (1000-based) or binary (1024-based) prefixes,
+// or skip scaling entirely and print the raw byte count.
+#[derive(PartialEq, Eq, ValueEnum, Clone, Copy)]
+pub enum Units {
+    Decimal,
+    Binary,
+    Bytes,
+}
+
 enum Unit {
+    Byte,
     Kilobyte,
     Megabyte,
     Gigabyte,
+    Terabyte,
 }
 
-impl fmt::Display for Unit {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let suffix = match self {
-            Unit::Kilobyte => "Kb",
-            Unit::Megabyte => "Mb",
-            Unit::Gigabyte => "Gb",
+impl Unit {
+    fn suffix(&self, units: Units) -> &'static str {
+        return match (self, units) {
+            (Unit::Byte, _) => "B",
+            (Unit::Kilobyte, Units::Binary) => "KiB",
+            (Unit::Kilobyte, _) => "Kb",
+            (Unit::Megabyte, Units::Binary) => "MiB",
+            (Unit::Megabyte, _) => "Mb",
+            (Unit::Gigabyte, Units::Binary) => "GiB",
+            (Unit::Gigabyte, _) => "Gb",
+            (Unit::Terabyte, Units::Binary) => "TiB",
+            (Unit::Terabyte, _) => "Tb",
         };
-        return write!(f, "{suffix}");
     }
 }
 
 pub struct Size {
     size: u64,
     unit: Unit,
+    units: Units,
 }
 
 impl Size {
-    pub fn new(size: u64) -> Self {
+    pub fn new(size: u64, units: Units) -> Self {
+        if units == Units::Bytes {
+            return Size { size, unit: Unit::Byte, units };
+        }
+
+        let base: u64 = if units == Units::Binary { 1024 } else { 1000 };
         let unit = match size {
-            0..1_000_000 => Unit::Kilobyte,
-            1_000_000..1_000_000_000 => Unit::Megabyte,
-            _ => Unit::Gigabyte,
+            _ if size < base.pow(2) => Unit::Kilobyte,
+            _ if size < base.pow(3) => Unit::Megabyte,
+            _ if size < base.pow(4) => Unit::Gigabyte,
+            _ => Unit::Terabyte,
         };
-        return Size { size, unit };
+        return Size { size, unit, units };
     }
 }
 
 impl fmt::Display for Size {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let rep_size = (self.size as f32)
-            / match self.unit {
-                Unit::Kilobyte => 1_000.0,
-                Unit::Megabyte => 1_000_000.0,
-                Unit::Gigabyte => 1_000_000_000.0,
-            };
-        return write!(f, "{:.2}{}", rep_size, self.unit);
+        if let Unit::Byte = self.unit {
+            return write!(f, "{}{}", self.size, self.unit.suffix(self.units));
+        }
+
+        let base: f32 = if self.units == Units::Binary { 1024.0 } else { 1000.0 };
+        let exponent = match self.unit {
+            Unit::Byte => 0,
+            Unit::Kilobyte => 1,
+            Unit::Megabyte => 2,
+            Unit::Gigabyte => 3,
+            Unit::Terabyte => 4,
+        };
+        let rep_size = (self.size as f32) / base.powi(exponent);
+        return write!(f, "{:.2}{}", rep_size, self.unit.suffix(self.units));
     }
 }